@@ -4,6 +4,41 @@ use rand;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::mem;
+use std::time::{Duration, Instant};
+
+const DEFAULT_RATING: f64 = 1200.0;
+const K_FACTOR: f64 = 32.0;
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A player's time budget for a game: an initial allotment plus a per-move
+/// increment, in the style of chess clocks.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub initial: Duration,
+    pub increment: Duration,
+}
+
+fn opposite_team(team: Team) -> Team {
+    match team {
+        Team::Light => Team::Dark,
+        Team::Dark => Team::Light,
+    }
+}
+
+const RECONNECT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// How long an unjoined `CreateRoom` code stays pending before it's swept
+/// away, so an abandoned code can't pin its owner's `Matchup` forever.
+const ROOM_TTL: Duration = Duration::from_secs(600);
+const ROOM_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Whether an `OngoingGame` is still being actively played.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum GameStatus {
+    Active,
+    AwaitingReconnect,
+    Finished,
+}
 
 #[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct PlayerID(pub usize);
@@ -11,8 +46,25 @@ pub struct PlayerID(pub usize);
 #[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct GameID(pub usize);
 
+/// Identifies one spectator subscription within a single game, so it can be
+/// deregistered later without relying on recipient identity.
+#[derive(Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct SpectatorId(pub usize);
+
+/// A short code a room owner shares out-of-band so a specific friend can
+/// join their private match.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RoomCode(pub String);
+
+struct PendingRoom {
+    owner: Matchup,
+    color_preference: Option<Team>,
+    created_at: Instant,
+}
+
 struct Matchmaker {
     enqueued: Option<Matchup>,
+    rooms: HashMap<RoomCode, PendingRoom>,
     last_match_id: GameID,
 }
 
@@ -20,22 +72,27 @@ impl Matchmaker {
     fn new() -> Self {
         Self {
             enqueued: None,
+            rooms: HashMap::new(),
             last_match_id: GameID(0),
         }
     }
 
+    fn next_game_id(&mut self) -> GameID {
+        let id = self.last_match_id;
+        self.last_match_id.0 += 1;
+        id
+    }
+
     fn matchup(&mut self, matchup: Matchup) -> Option<(Matchup, Matchup, GameID)> {
         match self.enqueued.take() {
             Some(enqueued_matchup) => {
-                self.last_match_id.0 += 1;
-
                 let mut light = matchup;
                 let mut dark = enqueued_matchup;
                 if rand::random() {
                     mem::swap(&mut light, &mut dark);
                 }
 
-                Some((light, dark, GameID(self.last_match_id.0 - 1)))
+                Some((light, dark, self.next_game_id()))
             }
             None => {
                 self.enqueued = Some(matchup);
@@ -43,18 +100,199 @@ impl Matchmaker {
             }
         }
     }
+
+    fn create_room(&mut self, matchup: Matchup, color_preference: Option<Team>) -> RoomCode {
+        let mut code = Self::generate_code();
+        while self.rooms.contains_key(&code) {
+            code = Self::generate_code();
+        }
+        self.rooms.insert(
+            code.clone(),
+            PendingRoom {
+                owner: matchup,
+                color_preference,
+                created_at: Instant::now(),
+            },
+        );
+        code
+    }
+
+    fn join_room(
+        &mut self,
+        code: &RoomCode,
+        matchup: Matchup,
+    ) -> Option<(Matchup, Matchup, GameID)> {
+        let room = self.rooms.remove(code)?;
+
+        let (light, dark) = match room.color_preference {
+            Some(Team::Light) => (room.owner, matchup),
+            Some(Team::Dark) => (matchup, room.owner),
+            None => {
+                let mut light = room.owner;
+                let mut dark = matchup;
+                if rand::random() {
+                    mem::swap(&mut light, &mut dark);
+                }
+                (light, dark)
+            }
+        };
+
+        Some((light, dark, self.next_game_id()))
+    }
+
+    fn generate_code() -> RoomCode {
+        RoomCode(format!("{:06X}", rand::random::<u32>() & 0xFF_FFFF))
+    }
+
+    /// Drops rooms nobody joined within `ROOM_TTL`, so an abandoned code
+    /// doesn't pin its owner's `Matchup` forever.
+    fn expire_rooms(&mut self) {
+        self.rooms
+            .retain(|_, room| room.created_at.elapsed() < ROOM_TTL);
+    }
+}
+
+/// A player's rating and game record, as tracked by a `Scoreboard`.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    pub rating: f64,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl Default for PlayerRecord {
+    fn default() -> Self {
+        Self {
+            rating: DEFAULT_RATING,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+        }
+    }
+}
+
+/// Backing storage for player ratings, so the scoreboard can later be
+/// swapped for something backed by a database without touching `GameMaster`.
+pub trait RatingStore {
+    fn get(&self, player_id: PlayerID) -> PlayerRecord;
+    fn set(&mut self, player_id: PlayerID, record: PlayerRecord);
+    fn all(&self) -> Vec<(PlayerID, PlayerRecord)>;
+}
+
+#[derive(Default)]
+struct InMemoryRatingStore {
+    records: HashMap<PlayerID, PlayerRecord>,
+}
+
+impl RatingStore for InMemoryRatingStore {
+    fn get(&self, player_id: PlayerID) -> PlayerRecord {
+        self.records.get(&player_id).copied().unwrap_or_default()
+    }
+
+    fn set(&mut self, player_id: PlayerID, record: PlayerRecord) {
+        self.records.insert(player_id, record);
+    }
+
+    fn all(&self) -> Vec<(PlayerID, PlayerRecord)> {
+        self.records
+            .iter()
+            .map(|(&id, &record)| (id, record))
+            .collect()
+    }
+}
+
+struct Scoreboard {
+    store: Box<dyn RatingStore>,
+}
+
+impl Scoreboard {
+    fn new() -> Self {
+        Self {
+            store: Box::new(InMemoryRatingStore::default()),
+        }
+    }
+
+    fn record(&self, player_id: PlayerID) -> PlayerRecord {
+        self.store.get(player_id)
+    }
+
+    fn record_result(&mut self, winner_id: PlayerID, loser_id: PlayerID) {
+        self.apply_elo(winner_id, loser_id, 1.0, 0.0);
+    }
+
+    fn apply_elo(&mut self, a_id: PlayerID, b_id: PlayerID, score_a: f64, score_b: f64) {
+        let mut a = self.store.get(a_id);
+        let mut b = self.store.get(b_id);
+
+        let expected_a = 1.0 / (1.0 + 10f64.powf((b.rating - a.rating) / 400.0));
+        let expected_b = 1.0 - expected_a;
+
+        a.rating += K_FACTOR * (score_a - expected_a);
+        b.rating += K_FACTOR * (score_b - expected_b);
+
+        match score_a.partial_cmp(&score_b) {
+            Some(std::cmp::Ordering::Greater) => {
+                a.wins += 1;
+                b.losses += 1;
+            }
+            Some(std::cmp::Ordering::Less) => {
+                a.losses += 1;
+                b.wins += 1;
+            }
+            _ => {
+                a.draws += 1;
+                b.draws += 1;
+            }
+        }
+
+        self.store.set(a_id, a);
+        self.store.set(b_id, b);
+    }
+
+    fn top(&self, n: usize) -> Vec<LeaderboardEntry> {
+        let mut entries: Vec<LeaderboardEntry> = self
+            .store
+            .all()
+            .into_iter()
+            .map(|(player_id, record)| LeaderboardEntry {
+                player_id,
+                rating: record.rating,
+            })
+            .collect();
+
+        entries.sort_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap());
+        entries.truncate(n);
+        entries
+    }
 }
 
 pub struct GameMaster {
     matchmaker: Matchmaker,
     games: HashMap<GameID, OngoingGame>,
     players_in_games: HashMap<PlayerID, GameID>,
+    scoreboard: Scoreboard,
+    last_chat_at: HashMap<PlayerID, Instant>,
 }
 
 struct OngoingGame {
     game: CheckersGame,
     light_player: Player,
     dark_player: Player,
+    time_control: Option<TimeControl>,
+    light_remaining: Duration,
+    dark_remaining: Duration,
+    turn_started: Instant,
+    /// Set when the game ends for a reason the underlying `CheckersGame`
+    /// doesn't know about, such as a flag fall or a resignation.
+    forced_winner: Option<Team>,
+    status: GameStatus,
+    disconnected_player: Option<PlayerID>,
+    /// Bumped on every disconnect so a stale grace-period timer from an
+    /// earlier disconnect can recognize that it's no longer current.
+    disconnect_epoch: u64,
+    spectators: Vec<SpectatorRecipients>,
+    next_spectator_id: usize,
 }
 
 struct Player {
@@ -62,23 +300,161 @@ struct Player {
     game_state_recipient: Recipient<GameState>,
     game_update_recipient: Recipient<GameUpdate>,
     bad_jump_recipient: Recipient<BadJump>,
+    flagged_recipient: Recipient<Flagged>,
+    chat_recipient: Recipient<ChatBroadcast>,
+}
+
+struct SpectatorRecipients {
+    id: SpectatorId,
+    game_state_recipient: Recipient<GameState>,
+    game_update_recipient: Recipient<GameUpdate>,
+    chat_recipient: Recipient<ChatBroadcast>,
 }
 
 impl OngoingGame {
-    fn jump(&mut self, player_id: PlayerID, from: usize, to: usize) {
+    /// Applies `player_id`'s jump and returns the winner if the game just
+    /// concluded as a result, so the caller can settle ratings.
+    fn jump(&mut self, player_id: PlayerID, from: usize, to: usize) -> Option<Team> {
+        if self.status != GameStatus::Active {
+            self.send_bad_jump(player_id);
+            return None;
+        }
         if self.is_on_turn(player_id) {
             match self.game.jump(from, to) {
                 JumpResult::Good {
                     captured_piece,
                     crowned,
-                } => self.send_updates(from, to, captured_piece, crowned),
-                JumpResult::Bad => self.send_bad_jump(player_id),
+                } => {
+                    self.tick_clock();
+                    self.send_updates(from, to, captured_piece, crowned);
+                    let winner = self.effective_winner();
+                    if winner.is_some() {
+                        self.status = GameStatus::Finished;
+                    }
+                    winner
+                }
+                JumpResult::Bad => {
+                    self.send_bad_jump(player_id);
+                    None
+                }
             }
         } else {
-            self.send_bad_jump(player_id)
+            self.send_bad_jump(player_id);
+            None
+        }
+    }
+
+    /// Charges the mover's clock for the time spent on the turn that just
+    /// ended, adds their increment, and restarts the clock for the player
+    /// now on turn.
+    fn tick_clock(&mut self) {
+        if self.time_control.is_none() {
+            return;
+        }
+        let mover = opposite_team(self.game.team_on_turn());
+        let elapsed = self.turn_started.elapsed();
+        let increment = self.time_control.unwrap().increment;
+        let remaining = self.remaining_mut(mover);
+        *remaining = remaining.saturating_sub(elapsed) + increment;
+        self.turn_started = Instant::now();
+    }
+
+    fn remaining(&self, team: Team) -> Duration {
+        match team {
+            Team::Light => self.light_remaining,
+            Team::Dark => self.dark_remaining,
+        }
+    }
+
+    fn remaining_mut(&mut self, team: Team) -> &mut Duration {
+        match team {
+            Team::Light => &mut self.light_remaining,
+            Team::Dark => &mut self.dark_remaining,
+        }
+    }
+
+    /// The team whose clock has just run out, if any.
+    fn timed_out_team(&self) -> Option<Team> {
+        self.time_control?;
+        let team_on_turn = self.game.team_on_turn();
+        if self.turn_started.elapsed() >= self.remaining(team_on_turn) {
+            Some(team_on_turn)
+        } else {
+            None
+        }
+    }
+
+    fn effective_winner(&self) -> Option<Team> {
+        self.forced_winner.or_else(|| self.game.winner())
+    }
+
+    fn player_id(&self, team: Team) -> PlayerID {
+        match team {
+            Team::Light => self.light_player.id,
+            Team::Dark => self.dark_player.id,
+        }
+    }
+
+    fn player_mut(&mut self, player_id: PlayerID) -> Option<&mut Player> {
+        if self.light_player.id == player_id {
+            Some(&mut self.light_player)
+        } else if self.dark_player.id == player_id {
+            Some(&mut self.dark_player)
+        } else {
+            None
         }
     }
 
+    /// Marks `player_id` as disconnected and returns the epoch identifying
+    /// this particular disconnect, so the caller's grace-period timer can
+    /// later tell whether it's still the most recent one.
+    fn disconnect(&mut self, player_id: PlayerID) -> u64 {
+        if self.status != GameStatus::Finished {
+            self.status = GameStatus::AwaitingReconnect;
+            self.disconnected_player = Some(player_id);
+        }
+        self.disconnect_epoch += 1;
+        self.disconnect_epoch
+    }
+
+    fn reconnect(&mut self, player_id: PlayerID, recipients: PlayerRecipients) -> bool {
+        if self.disconnected_player != Some(player_id) {
+            return false;
+        }
+        if let Some(player) = self.player_mut(player_id) {
+            player.game_state_recipient = recipients.game_state_recipient;
+            player.game_update_recipient = recipients.game_update_recipient;
+            player.bad_jump_recipient = recipients.bad_jump_recipient;
+            player.flagged_recipient = recipients.flagged_recipient;
+            player.chat_recipient = recipients.chat_recipient;
+        }
+        self.disconnected_player = None;
+        self.status = GameStatus::Active;
+        true
+    }
+
+    /// Registers a spectator and returns the id used to remove it later.
+    fn add_spectator(
+        &mut self,
+        game_state_recipient: Recipient<GameState>,
+        game_update_recipient: Recipient<GameUpdate>,
+        chat_recipient: Recipient<ChatBroadcast>,
+    ) -> SpectatorId {
+        let id = SpectatorId(self.next_spectator_id);
+        self.next_spectator_id += 1;
+        self.spectators.push(SpectatorRecipients {
+            id,
+            game_state_recipient,
+            game_update_recipient,
+            chat_recipient,
+        });
+        id
+    }
+
+    fn remove_spectator(&mut self, id: SpectatorId) {
+        self.spectators.retain(|s| s.id != id);
+    }
+
     fn is_on_turn(&self, player_id: PlayerID) -> bool {
         match self.team(player_id) {
             Some(team) => team == self.game.team_on_turn(),
@@ -106,17 +482,45 @@ impl OngoingGame {
         }
     }
 
-    fn send_game_state(&self) {
-        let msg = GameState {
+    fn game_state_recipients(&self) -> impl Iterator<Item = &Recipient<GameState>> {
+        std::iter::once(&self.light_player.game_state_recipient)
+            .chain(std::iter::once(&self.dark_player.game_state_recipient))
+            .chain(self.spectators.iter().map(|s| &s.game_state_recipient))
+    }
+
+    fn game_update_recipients(&self) -> impl Iterator<Item = &Recipient<GameUpdate>> {
+        std::iter::once(&self.light_player.game_update_recipient)
+            .chain(std::iter::once(&self.dark_player.game_update_recipient))
+            .chain(self.spectators.iter().map(|s| &s.game_update_recipient))
+    }
+
+    fn chat_recipients(&self) -> impl Iterator<Item = &Recipient<ChatBroadcast>> {
+        std::iter::once(&self.light_player.chat_recipient)
+            .chain(std::iter::once(&self.dark_player.chat_recipient))
+            .chain(self.spectators.iter().map(|s| &s.chat_recipient))
+    }
+
+    fn current_game_state(&self) -> GameState {
+        GameState {
             table: *self.game.table(),
             team_on_turn: self.game.team_on_turn(),
-            winner: self.game.winner(),
-        };
-        self.light_player
-            .game_state_recipient
-            .do_send(msg.clone())
-            .unwrap();
-        self.dark_player.game_state_recipient.do_send(msg).unwrap();
+            winner: self.effective_winner(),
+            light_remaining_millis: self
+                .time_control
+                .map(|_| self.light_remaining.as_millis() as u64),
+            dark_remaining_millis: self
+                .time_control
+                .map(|_| self.dark_remaining.as_millis() as u64),
+        }
+    }
+
+    fn send_game_state(&self) {
+        let msg = self.current_game_state();
+        for recipient in self.game_state_recipients() {
+            // A disconnected player's mailbox may already be closed; that's
+            // not this game's problem to panic over.
+            let _ = recipient.do_send(msg.clone());
+        }
     }
 
     fn send_updates(&self, from: usize, to: usize, captured_piece: Option<usize>, crowned: bool) {
@@ -126,35 +530,78 @@ impl OngoingGame {
             crowned,
             captured_piece,
             team_on_turn: self.game.team_on_turn(),
-            winner: self.game.winner(),
+            winner: self.effective_winner(),
+            light_remaining_millis: self
+                .time_control
+                .map(|_| self.light_remaining.as_millis() as u64),
+            dark_remaining_millis: self
+                .time_control
+                .map(|_| self.dark_remaining.as_millis() as u64),
         };
 
-        self.light_player
-            .game_update_recipient
-            .do_send(msg.clone())
-            .unwrap();
-        self.dark_player.game_update_recipient.do_send(msg).unwrap();
+        for recipient in self.game_update_recipients() {
+            let _ = recipient.do_send(msg.clone());
+        }
     }
 
     fn send_bad_jump(&self, player_id: PlayerID) {
         if let Some(r) = self.bad_jump_recipient(player_id) {
-            r.do_send(BadJump).unwrap();
+            let _ = r.do_send(BadJump);
+        }
+    }
+
+    fn send_flagged(&self, loser: PlayerID) {
+        let msg = Flagged { loser };
+        let _ = self.light_player.flagged_recipient.do_send(msg.clone());
+        let _ = self.dark_player.flagged_recipient.do_send(msg);
+    }
+
+    fn send_chat(&self, from: PlayerID, text: String) {
+        let msg = ChatBroadcast { from, text };
+        for recipient in self.chat_recipients() {
+            let _ = recipient.do_send(msg.clone());
         }
     }
 }
 
 pub type GameMasterAddr = Addr<GameMaster>;
 
-#[derive(Message)]
-#[rtype(result = "()")]
+/// A player's recipients and preferences for whatever game they end up in,
+/// regardless of which matchmaking path found it.
 pub struct Matchup {
     pub game_found_recipient: Recipient<GameFound>,
     pub game_update_recipient: Recipient<GameUpdate>,
     pub game_state_recipient: Recipient<GameState>,
     pub bad_jump_recipient: Recipient<BadJump>,
+    pub flagged_recipient: Recipient<Flagged>,
+    pub chat_recipient: Recipient<ChatBroadcast>,
     pub player_id: PlayerID,
+    pub time_control: Option<TimeControl>,
 }
 
+/// Joins the anonymous matchmaking queue, pairing with whoever is next in
+/// line.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct QuickMatch(pub Matchup);
+
+#[derive(Message)]
+#[rtype(result = "RoomCode")]
+pub struct CreateRoom {
+    pub matchup: Matchup,
+    pub color_preference: Option<Team>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<(), RoomNotFound>")]
+pub struct JoinRoom {
+    pub code: RoomCode,
+    pub matchup: Matchup,
+}
+
+#[derive(Debug)]
+pub struct RoomNotFound;
+
 #[derive(Message, Serialize)]
 #[rtype(result = "()")]
 pub struct GameFound {
@@ -177,6 +624,8 @@ pub struct GameState {
     table: Table,
     team_on_turn: Team,
     winner: Option<Team>,
+    light_remaining_millis: Option<u64>,
+    dark_remaining_millis: Option<u64>,
 }
 
 #[derive(Message, Clone, Serialize)]
@@ -188,69 +637,297 @@ pub struct GameUpdate {
     captured_piece: Option<usize>,
     team_on_turn: Team,
     winner: Option<Team>,
+    light_remaining_millis: Option<u64>,
+    dark_remaining_millis: Option<u64>,
 }
 
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct BadJump;
 
+#[derive(Message, Clone, Serialize)]
+#[rtype(result = "()")]
+pub struct Flagged {
+    pub loser: PlayerID,
+}
+
+/// The recipients a reconnecting client replaces its stale ones with.
+pub struct PlayerRecipients {
+    pub game_state_recipient: Recipient<GameState>,
+    pub game_update_recipient: Recipient<GameUpdate>,
+    pub bad_jump_recipient: Recipient<BadJump>,
+    pub flagged_recipient: Recipient<Flagged>,
+    pub chat_recipient: Recipient<ChatBroadcast>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PlayerDisconnected {
+    pub player_id: PlayerID,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PlayerReconnected {
+    pub player_id: PlayerID,
+    pub new_recipients: PlayerRecipients,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Resign {
+    pub player_id: PlayerID,
+}
+
+#[derive(Message)]
+#[rtype(result = "Option<SpectatorId>")]
+pub struct Spectate {
+    pub game_id: GameID,
+    pub game_state_recipient: Recipient<GameState>,
+    pub game_update_recipient: Recipient<GameUpdate>,
+    pub chat_recipient: Recipient<ChatBroadcast>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct StopSpectating {
+    pub game_id: GameID,
+    pub spectator_id: SpectatorId,
+}
+
+/// Caps applied to in-game chat to keep it from being abused as a spam
+/// channel.
+const CHAT_MAX_LEN: usize = 280;
+const CHAT_MIN_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ChatMessage {
+    pub player_id: PlayerID,
+    pub text: String,
+}
+
+#[derive(Message, Clone, Serialize)]
+#[rtype(result = "()")]
+pub struct ChatBroadcast {
+    pub from: PlayerID,
+    pub text: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "PlayerRating")]
+pub struct QueryRating {
+    pub player_id: PlayerID,
+}
+
+#[derive(Clone, Serialize)]
+pub struct PlayerRating {
+    pub rating: f64,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+#[derive(Message)]
+#[rtype(result = "Vec<LeaderboardEntry>")]
+pub struct Leaderboard {
+    pub top_n: usize,
+}
+
+#[derive(Clone, Serialize)]
+pub struct LeaderboardEntry {
+    pub player_id: PlayerID,
+    pub rating: f64,
+}
+
 impl GameMaster {
     pub fn new() -> Self {
         Self {
             matchmaker: Matchmaker::new(),
             games: HashMap::new(),
             players_in_games: HashMap::new(),
+            scoreboard: Scoreboard::new(),
+            last_chat_at: HashMap::new(),
+        }
+    }
+
+    /// Settles ratings for a game that just ended with `winner` on top.
+    fn settle_rating(&mut self, game: &OngoingGame, winner: Team) {
+        let winner_id = game.player_id(winner);
+        let loser_id = game.player_id(opposite_team(winner));
+        self.scoreboard.record_result(winner_id, loser_id);
+    }
+
+    /// Checks every ongoing game's clock and flags whichever player on turn
+    /// has run out of time.
+    fn check_clocks(&mut self, _: &mut Context<Self>) {
+        let newly_flagged: Vec<(GameID, Team)> = self
+            .games
+            .iter()
+            .filter(|(_, game)| game.status == GameStatus::Active && game.game.winner().is_none())
+            .filter_map(|(id, game)| game.timed_out_team().map(|loser| (*id, loser)))
+            .collect();
+
+        for (game_id, loser_team) in newly_flagged {
+            let winner_team = opposite_team(loser_team);
+
+            if let Some(game) = self.games.get_mut(&game_id) {
+                game.forced_winner = Some(winner_team);
+                game.status = GameStatus::Finished;
+                let loser_id = game.player_id(loser_team);
+                game.send_game_state();
+                game.send_flagged(loser_id);
+            }
+
+            if let Some(game) = self.games.get(&game_id) {
+                self.settle_rating(game, winner_team);
+            }
         }
     }
+
+    /// Forfeits `player_id` out of `game_id` if they're still the one the
+    /// game is awaiting a reconnect from once the grace period elapses, and
+    /// `disconnect_epoch` still matches the disconnect that scheduled this
+    /// timer (a later disconnect bumps the epoch and supersedes it).
+    fn forfeit_disconnected(
+        &mut self,
+        game_id: GameID,
+        player_id: PlayerID,
+        disconnect_epoch: u64,
+        _: &mut Context<Self>,
+    ) {
+        let winner_team = match self.games.get(&game_id) {
+            Some(game)
+                if game.status == GameStatus::AwaitingReconnect
+                    && game.disconnected_player == Some(player_id)
+                    && game.disconnect_epoch == disconnect_epoch =>
+            {
+                game.team(player_id).map(opposite_team)
+            }
+            _ => None,
+        };
+
+        if let Some(winner_team) = winner_team {
+            if let Some(game) = self.games.get_mut(&game_id) {
+                game.forced_winner = Some(winner_team);
+                game.status = GameStatus::Finished;
+                game.send_game_state();
+            }
+            if let Some(game) = self.games.get(&game_id) {
+                self.settle_rating(game, winner_team);
+            }
+        }
+    }
+
+    /// Sweeps out `CreateRoom` codes nobody joined within `ROOM_TTL`.
+    fn expire_rooms(&mut self, _: &mut Context<Self>) {
+        self.matchmaker.expire_rooms();
+    }
 }
 
 impl Actor for GameMaster {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(CLOCK_CHECK_INTERVAL, Self::check_clocks);
+        ctx.run_interval(ROOM_SWEEP_INTERVAL, Self::expire_rooms);
+    }
+}
+
+impl GameMaster {
+    /// Starts an `OngoingGame` for a paired-up `light`/`dark` and notifies
+    /// both players, regardless of which matchmaking path found them each
+    /// other.
+    fn start_game(&mut self, light: Matchup, dark: Matchup, game_id: GameID) {
+        let time_control = light.time_control.or(dark.time_control);
+        let initial = time_control
+            .map(|tc| tc.initial)
+            .unwrap_or_else(|| Duration::from_secs(0));
+
+        let game = OngoingGame {
+            light_player: Player {
+                id: light.player_id,
+                game_state_recipient: light.game_state_recipient,
+                game_update_recipient: light.game_update_recipient,
+                bad_jump_recipient: light.bad_jump_recipient,
+                flagged_recipient: light.flagged_recipient,
+                chat_recipient: light.chat_recipient,
+            },
+            dark_player: Player {
+                id: dark.player_id,
+                game_state_recipient: dark.game_state_recipient,
+                game_update_recipient: dark.game_update_recipient,
+                bad_jump_recipient: dark.bad_jump_recipient,
+                flagged_recipient: dark.flagged_recipient,
+                chat_recipient: dark.chat_recipient,
+            },
+            game: CheckersGame::new(),
+            time_control,
+            light_remaining: initial,
+            dark_remaining: initial,
+            turn_started: Instant::now(),
+            forced_winner: None,
+            status: GameStatus::Active,
+            disconnected_player: None,
+            disconnect_epoch: 0,
+            spectators: Vec::new(),
+            next_spectator_id: 0,
+        };
+
+        game.send_game_state();
+
+        self.games.insert(game_id, game);
+        self.players_in_games.insert(light.player_id, game_id);
+        self.players_in_games.insert(dark.player_id, game_id);
+
+        light
+            .game_found_recipient
+            .do_send(GameFound {
+                game_id,
+                light_player: light.player_id,
+                dark_player: dark.player_id,
+            })
+            .unwrap();
+
+        dark.game_found_recipient
+            .do_send(GameFound {
+                game_id,
+                light_player: light.player_id,
+                dark_player: dark.player_id,
+            })
+            .unwrap();
+    }
 }
 
-impl Handler<Matchup> for GameMaster {
+impl Handler<QuickMatch> for GameMaster {
     type Result = ();
 
-    fn handle(&mut self, msg: Matchup, _: &mut Self::Context) {
-        if let Some((light, dark, game_id)) = self.matchmaker.matchup(msg) {
-            let game = OngoingGame {
-                light_player: Player {
-                    id: light.player_id,
-                    game_state_recipient: light.game_state_recipient,
-                    game_update_recipient: light.game_update_recipient,
-                    bad_jump_recipient: light.bad_jump_recipient,
-                },
-                dark_player: Player {
-                    id: dark.player_id,
-                    game_state_recipient: dark.game_state_recipient,
-                    game_update_recipient: dark.game_update_recipient,
-                    bad_jump_recipient: dark.bad_jump_recipient,
-                },
-                game: CheckersGame::new(),
-            };
+    fn handle(&mut self, msg: QuickMatch, _: &mut Self::Context) {
+        if let Some((light, dark, game_id)) = self.matchmaker.matchup(msg.0) {
+            self.start_game(light, dark, game_id);
+        }
+    }
+}
 
-            game.send_game_state();
+impl Handler<CreateRoom> for GameMaster {
+    type Result = RoomCode;
 
-            self.games.insert(game_id, game);
-            self.players_in_games.insert(light.player_id, game_id);
-            self.players_in_games.insert(dark.player_id, game_id);
+    fn handle(&mut self, msg: CreateRoom, _: &mut Self::Context) -> Self::Result {
+        self.matchmaker
+            .create_room(msg.matchup, msg.color_preference)
+    }
+}
 
-            light
-                .game_found_recipient
-                .do_send(GameFound {
-                    game_id,
-                    light_player: light.player_id,
-                    dark_player: dark.player_id,
-                })
-                .unwrap();
+impl Handler<JoinRoom> for GameMaster {
+    type Result = Result<(), RoomNotFound>;
 
-            dark.game_found_recipient
-                .do_send(GameFound {
-                    game_id,
-                    light_player: light.player_id,
-                    dark_player: dark.player_id,
-                })
-                .unwrap();
+    fn handle(&mut self, msg: JoinRoom, _: &mut Self::Context) -> Self::Result {
+        match self.matchmaker.join_room(&msg.code, msg.matchup) {
+            Some((light, dark, game_id)) => {
+                self.start_game(light, dark, game_id);
+                Ok(())
+            }
+            None => Err(RoomNotFound),
         }
     }
 }
@@ -261,14 +938,337 @@ impl Handler<Jump> for GameMaster {
     fn handle(&mut self, msg: Jump, _: &mut Self::Context) -> Self::Result {
         match self.players_in_games.get(&msg.player_id) {
             Some(game_id) => {
+                let game_id = *game_id;
                 let game = self
                     .games
-                    .get_mut(game_id)
+                    .get_mut(&game_id)
                     .expect("player was in a non-existent game");
 
-                game.jump(msg.player_id, msg.from, msg.to);
+                if let Some(winner) = game.jump(msg.player_id, msg.from, msg.to) {
+                    let game = self.games.get(&game_id).unwrap();
+                    self.settle_rating(game, winner);
+                }
             }
-            None => unimplemented!(),
+            // Stale/duplicate client message, or a sender we never matched
+            // into a game: nothing to do.
+            None => {}
+        }
+    }
+}
+
+impl Handler<QueryRating> for GameMaster {
+    type Result = PlayerRating;
+
+    fn handle(&mut self, msg: QueryRating, _: &mut Self::Context) -> Self::Result {
+        let record = self.scoreboard.record(msg.player_id);
+        PlayerRating {
+            rating: record.rating,
+            wins: record.wins,
+            losses: record.losses,
+            draws: record.draws,
         }
     }
 }
+
+impl Handler<Leaderboard> for GameMaster {
+    type Result = Vec<LeaderboardEntry>;
+
+    fn handle(&mut self, msg: Leaderboard, _: &mut Self::Context) -> Self::Result {
+        self.scoreboard.top(msg.top_n)
+    }
+}
+
+impl Handler<PlayerDisconnected> for GameMaster {
+    type Result = ();
+
+    fn handle(&mut self, msg: PlayerDisconnected, ctx: &mut Self::Context) {
+        if let Some(&game_id) = self.players_in_games.get(&msg.player_id) {
+            let disconnect_epoch = match self.games.get_mut(&game_id) {
+                Some(game) => game.disconnect(msg.player_id),
+                None => return,
+            };
+
+            let player_id = msg.player_id;
+            ctx.run_later(RECONNECT_GRACE_PERIOD, move |act, ctx| {
+                act.forfeit_disconnected(game_id, player_id, disconnect_epoch, ctx);
+            });
+        }
+    }
+}
+
+impl Handler<PlayerReconnected> for GameMaster {
+    type Result = ();
+
+    fn handle(&mut self, msg: PlayerReconnected, _: &mut Self::Context) {
+        if let Some(&game_id) = self.players_in_games.get(&msg.player_id) {
+            if let Some(game) = self.games.get_mut(&game_id) {
+                if game.reconnect(msg.player_id, msg.new_recipients) {
+                    game.send_game_state();
+                }
+            }
+        }
+    }
+}
+
+impl Handler<Resign> for GameMaster {
+    type Result = ();
+
+    fn handle(&mut self, msg: Resign, _: &mut Self::Context) {
+        if let Some(&game_id) = self.players_in_games.get(&msg.player_id) {
+            let winner_team = self
+                .games
+                .get(&game_id)
+                .filter(|game| game.status != GameStatus::Finished)
+                .and_then(|game| game.team(msg.player_id))
+                .map(opposite_team);
+
+            if let Some(winner_team) = winner_team {
+                if let Some(game) = self.games.get_mut(&game_id) {
+                    game.forced_winner = Some(winner_team);
+                    game.status = GameStatus::Finished;
+                    game.send_game_state();
+                }
+                if let Some(game) = self.games.get(&game_id) {
+                    self.settle_rating(game, winner_team);
+                }
+            }
+        }
+    }
+}
+
+impl Handler<Spectate> for GameMaster {
+    type Result = Option<SpectatorId>;
+
+    fn handle(&mut self, msg: Spectate, _: &mut Self::Context) -> Self::Result {
+        let game = self.games.get_mut(&msg.game_id)?;
+        msg.game_state_recipient
+            .do_send(game.current_game_state())
+            .unwrap();
+
+        Some(game.add_spectator(
+            msg.game_state_recipient,
+            msg.game_update_recipient,
+            msg.chat_recipient,
+        ))
+    }
+}
+
+impl Handler<StopSpectating> for GameMaster {
+    type Result = ();
+
+    fn handle(&mut self, msg: StopSpectating, _: &mut Self::Context) {
+        if let Some(game) = self.games.get_mut(&msg.game_id) {
+            game.remove_spectator(msg.spectator_id);
+        }
+    }
+}
+
+impl Handler<ChatMessage> for GameMaster {
+    type Result = ();
+
+    fn handle(&mut self, msg: ChatMessage, _: &mut Self::Context) {
+        if msg.text.is_empty() || msg.text.len() > CHAT_MAX_LEN {
+            return;
+        }
+
+        let now = Instant::now();
+        if let Some(last) = self.last_chat_at.get(&msg.player_id) {
+            if now.duration_since(*last) < CHAT_MIN_INTERVAL {
+                return;
+            }
+        }
+
+        if let Some(&game_id) = self.players_in_games.get(&msg.player_id) {
+            if let Some(game) = self.games.get(&game_id) {
+                game.send_chat(msg.player_id, msg.text);
+                self.last_chat_at.insert(msg.player_id, now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn win_moves_the_winner_up_and_the_loser_down() {
+        let mut scoreboard = Scoreboard::new();
+        let winner = PlayerID(1);
+        let loser = PlayerID(2);
+
+        scoreboard.record_result(winner, loser);
+
+        let winner_record = scoreboard.record(winner);
+        let loser_record = scoreboard.record(loser);
+        assert!(winner_record.rating > DEFAULT_RATING);
+        assert!(loser_record.rating < DEFAULT_RATING);
+        assert_eq!(winner_record.wins, 1);
+        assert_eq!(winner_record.losses, 0);
+        assert_eq!(loser_record.wins, 0);
+        assert_eq!(loser_record.losses, 1);
+    }
+
+    #[test]
+    fn draw_between_equally_rated_players_leaves_ratings_unchanged() {
+        let mut scoreboard = Scoreboard::new();
+        let a = PlayerID(1);
+        let b = PlayerID(2);
+
+        scoreboard.apply_elo(a, b, 0.5, 0.5);
+
+        let a_record = scoreboard.record(a);
+        let b_record = scoreboard.record(b);
+        assert_eq!(a_record.rating, DEFAULT_RATING);
+        assert_eq!(b_record.rating, DEFAULT_RATING);
+        assert_eq!(a_record.draws, 1);
+        assert_eq!(b_record.draws, 1);
+    }
+
+    struct Sink;
+
+    impl Actor for Sink {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<GameFound> for Sink {
+        type Result = ();
+        fn handle(&mut self, _: GameFound, _: &mut Self::Context) {}
+    }
+
+    impl Handler<GameState> for Sink {
+        type Result = ();
+        fn handle(&mut self, _: GameState, _: &mut Self::Context) {}
+    }
+
+    impl Handler<GameUpdate> for Sink {
+        type Result = ();
+        fn handle(&mut self, _: GameUpdate, _: &mut Self::Context) {}
+    }
+
+    impl Handler<BadJump> for Sink {
+        type Result = ();
+        fn handle(&mut self, _: BadJump, _: &mut Self::Context) {}
+    }
+
+    impl Handler<Flagged> for Sink {
+        type Result = ();
+        fn handle(&mut self, _: Flagged, _: &mut Self::Context) {}
+    }
+
+    impl Handler<ChatBroadcast> for Sink {
+        type Result = ();
+        fn handle(&mut self, _: ChatBroadcast, _: &mut Self::Context) {}
+    }
+
+    fn matchup(
+        player_id: PlayerID,
+        sink: &Addr<Sink>,
+        time_control: Option<TimeControl>,
+    ) -> Matchup {
+        Matchup {
+            game_found_recipient: sink.clone().recipient(),
+            game_update_recipient: sink.clone().recipient(),
+            game_state_recipient: sink.clone().recipient(),
+            bad_jump_recipient: sink.clone().recipient(),
+            flagged_recipient: sink.clone().recipient(),
+            chat_recipient: sink.clone().recipient(),
+            player_id,
+            time_control,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn stale_disconnect_timer_cannot_forfeit_after_a_fresh_disconnect() {
+        let sink = Sink.start();
+        let light = matchup(PlayerID(1), &sink, None);
+        let dark = matchup(PlayerID(2), &sink, None);
+        let light_id = light.player_id;
+        let game_id = GameID(0);
+
+        let mut game_master = GameMaster::new();
+        game_master.start_game(light, dark, game_id);
+
+        // The player disconnects, reconnects before the grace period
+        // elapses, then disconnects again. The first disconnect's timer is
+        // now stale and must not be the one that forfeits them.
+        let stale_epoch = game_master
+            .games
+            .get_mut(&game_id)
+            .unwrap()
+            .disconnect(light_id);
+
+        let reconnected = game_master.games.get_mut(&game_id).unwrap().reconnect(
+            light_id,
+            PlayerRecipients {
+                game_state_recipient: sink.clone().recipient(),
+                game_update_recipient: sink.clone().recipient(),
+                bad_jump_recipient: sink.clone().recipient(),
+                flagged_recipient: sink.clone().recipient(),
+                chat_recipient: sink.clone().recipient(),
+            },
+        );
+        assert!(reconnected);
+
+        let fresh_epoch = game_master
+            .games
+            .get_mut(&game_id)
+            .unwrap()
+            .disconnect(light_id);
+        assert_ne!(stale_epoch, fresh_epoch);
+
+        let mut ctx = Context::new();
+        game_master.forfeit_disconnected(game_id, light_id, stale_epoch, &mut ctx);
+        assert_eq!(
+            game_master.games.get(&game_id).unwrap().status,
+            GameStatus::AwaitingReconnect,
+            "the stale timer must not have forfeited the player"
+        );
+
+        game_master.forfeit_disconnected(game_id, light_id, fresh_epoch, &mut ctx);
+        assert_eq!(
+            game_master.games.get(&game_id).unwrap().status,
+            GameStatus::Finished,
+            "the current timer is the one that should forfeit the player"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn check_clocks_does_not_reflag_a_game_that_already_concluded() {
+        let sink = Sink.start();
+        let light = matchup(
+            PlayerID(1),
+            &sink,
+            Some(TimeControl {
+                initial: Duration::from_secs(60),
+                increment: Duration::from_secs(0),
+            }),
+        );
+        let dark = matchup(PlayerID(2), &sink, None);
+        let game_id = GameID(0);
+
+        let mut game_master = GameMaster::new();
+        game_master.start_game(light, dark, game_id);
+
+        {
+            // Simulate the game having just concluded naturally (or via
+            // resignation/forfeit) right as its clock happened to run dry,
+            // racing the periodic clock sweep.
+            let game = game_master.games.get_mut(&game_id).unwrap();
+            game.light_remaining = Duration::from_secs(0);
+            game.dark_remaining = Duration::from_secs(0);
+            game.forced_winner = Some(Team::Light);
+            game.status = GameStatus::Finished;
+        }
+
+        let mut ctx = Context::new();
+        game_master.check_clocks(&mut ctx);
+
+        let game = game_master.games.get(&game_id).unwrap();
+        assert_eq!(game.status, GameStatus::Finished);
+        assert!(
+            game.forced_winner == Some(Team::Light),
+            "check_clocks must not overwrite a game that's already finished"
+        );
+    }
+}